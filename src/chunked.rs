@@ -0,0 +1,254 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+
+use crate::probe;
+use crate::vmaf;
+use crate::Cli;
+
+/// Runs an ffmpeg scene-change filter over `video` and returns the timestamps (in
+/// seconds) where the scene score exceeds `threshold`, parsed out of `showinfo`'s
+/// `pts_time:` fields on stderr.
+fn detect_scene_boundaries(video: &Path, threshold: f64) -> Vec<f64> {
+    let output = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("info")
+        .arg("-i")
+        .arg(video)
+        .arg("-vf")
+        .arg(format!("select='gt(scene,{})',showinfo", threshold))
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .output();
+
+    let Ok(output) = output else { return Vec::new() };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let mut timestamps = Vec::new();
+    for line in stderr.lines() {
+        if let Some(pos) = line.find("pts_time:") {
+            let rest = &line[pos + "pts_time:".len()..];
+            if let Some(value) = rest.split_whitespace().next() {
+                if let Ok(timestamp) = value.parse::<f64>() {
+                    timestamps.push(timestamp);
+                }
+            }
+        }
+    }
+    timestamps
+}
+
+/// Turns a sorted list of scene-change timestamps into `(start, end)` segment ranges
+/// spanning the whole file.
+fn segment_ranges(boundaries: &[f64], duration: f64) -> Vec<(f64, f64)> {
+    let mut points: Vec<f64> = std::iter::once(0.0).chain(boundaries.iter().copied()).collect();
+    points.push(duration);
+    points.dedup_by(|a, b| (*a - *b).abs() < 0.01);
+
+    points.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Losslessly extracts `video[start, end)` into `output`.
+fn extract_segment(video: &Path, start: f64, end: f64, output: &Path) -> bool {
+    Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-ss")
+        .arg(format!("{:.3}", start))
+        .arg("-to")
+        .arg(format!("{:.3}", end))
+        .arg("-i")
+        .arg(video)
+        .arg("-c")
+        .arg("copy")
+        .arg(output)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Encodes a single segment through HandBrakeCLI, optionally driven by a VMAF-targeted
+/// CQ search, mirroring the quality selection `convert_video` uses for whole files.
+fn encode_segment(segment: &Path, output: &Path, cli_parse: &Cli, preset_file: &Path) -> bool {
+    let mut handbrake_command = Command::new("HandBrakeCLI");
+    handbrake_command
+        .arg("-i")
+        .arg(segment)
+        .arg("-o")
+        .arg(output)
+        .arg("--preset-import-file")
+        .arg(preset_file)
+        .arg("--preset")
+        .arg("Fast 1080p NVENC");
+
+    if let Some(target_vmaf) = cli_parse.target_vmaf {
+        let cq = vmaf::find_quality_for_target(
+            segment,
+            target_vmaf,
+            cli_parse.vmaf_tolerance,
+            cli_parse.cq_min,
+            cli_parse.cq_max,
+            cli_parse.debug,
+        );
+        handbrake_command.arg("--quality").arg(cq.to_string());
+    }
+
+    handbrake_command.output().map(|o| o.status.success()).unwrap_or(false)
+}
+
+/// Losslessly concatenates `segments` (in order) into `output` via the ffmpeg concat demuxer.
+fn concat_segments(segments: &[PathBuf], output: &Path) -> bool {
+    let list_file = output.with_extension("concat.txt");
+    let Ok(mut file) = fs::File::create(&list_file) else { return false };
+    for segment in segments {
+        if writeln!(file, "file '{}'", segment.display()).is_err() {
+            return false;
+        }
+    }
+    drop(file);
+
+    let result = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-v")
+        .arg("error")
+        .arg("-f")
+        .arg("concat")
+        .arg("-safe")
+        .arg("0")
+        .arg("-i")
+        .arg(&list_file)
+        .arg("-c")
+        .arg("copy")
+        .arg(output)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+
+    let _ = fs::remove_file(&list_file);
+    result
+}
+
+/// Returns how many chunks `video` would be split into at `scene_threshold`, so callers
+/// can size a progress bar across multiple files before encoding starts.
+pub fn count_segments(video: &Path, scene_threshold: f64) -> usize {
+    let duration = match probe::probe(video) {
+        Ok(media_info) => media_info.format.duration.and_then(|d| d.parse::<f64>().ok()),
+        Err(_) => None,
+    };
+    let Some(duration) = duration else { return 1 };
+    let boundaries = detect_scene_boundaries(video, scene_threshold);
+    segment_ranges(&boundaries, duration).len()
+}
+
+/// Splits `video` at its detected scene-change boundaries, encodes the resulting
+/// segments in parallel through a `workers`-sized rayon thread pool, then losslessly
+/// reassembles them and remuxes the original file's subtitle tracks (not its audio) back in.
+///
+/// `pb` is advanced once per completed chunk rather than once per file, so long encodes
+/// show real-time progress.
+pub fn convert_video_chunked(video: &Path, cli_parse: &Cli, workers: usize, scene_threshold: f64, pb: &ProgressBar) {
+    let duration = match probe::probe(video) {
+        Ok(media_info) => media_info.format.duration.and_then(|d| d.parse::<f64>().ok()),
+        Err(e) => {
+            println!("Warning | {}", e);
+            None
+        }
+    };
+    let Some(duration) = duration else {
+        println!("Warning | Could not determine duration for {}, skipping chunked encode.", video.display());
+        return;
+    };
+
+    let boundaries = detect_scene_boundaries(video, scene_threshold);
+    let ranges = segment_ranges(&boundaries, duration);
+
+    let preset_file = std::env::current_dir()
+        .expect("ERROR | Could not get current working directory.")
+        .join("presets.json");
+
+    let source_segments: Vec<PathBuf> = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, _)| video.with_file_name(format!("chunk_src_{}.mkv", i)))
+        .collect();
+    let encoded_segments: Vec<PathBuf> = ranges
+        .iter()
+        .enumerate()
+        .map(|(i, _)| video.with_file_name(format!("chunk_enc_{}.mkv", i)))
+        .collect();
+
+    for (i, (start, end)) in ranges.iter().enumerate() {
+        if !extract_segment(video, *start, *end, &source_segments[i]) {
+            println!("Warning | Failed to extract chunk {} of {}, aborting chunked encode.", i, video.display());
+            cleanup(&source_segments);
+            cleanup(&encoded_segments);
+            return;
+        }
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .build()
+        .expect("Failed to build thread pool");
+
+    let results: Vec<bool> = pool.install(|| {
+        source_segments
+            .par_iter()
+            .zip(encoded_segments.par_iter())
+            .map(|(source, encoded)| {
+                let success = encode_segment(source, encoded, cli_parse, &preset_file);
+                pb.inc(1);
+                success
+            })
+            .collect()
+    });
+
+    cleanup(&source_segments);
+
+    if results.iter().any(|success| !success) {
+        println!("Warning | One or more chunks failed to encode for {}, leaving original file untouched.", video.display());
+        cleanup(&encoded_segments);
+        return;
+    }
+
+    let combined_file = video.with_file_name("combined_chunks.mkv");
+    if !concat_segments(&encoded_segments, &combined_file) {
+        println!("Warning | Failed to reassemble chunks for {}.", video.display());
+        cleanup(&encoded_segments);
+        let _ = fs::remove_file(&combined_file);
+        return;
+    }
+    cleanup(&encoded_segments);
+
+    let new_file = video.with_file_name("new_file.mkv");
+    let mkvmerge_command = Command::new("mkvmerge")
+        .arg("-o")
+        .arg(&new_file)
+        .arg("-D")
+        .arg("-A")
+        .arg(video)
+        .arg("-S")
+        .arg("-B")
+        .arg("-T")
+        .arg("-M")
+        .arg(&combined_file)
+        .output();
+
+    if mkvmerge_command.map(|o| o.status.success()).unwrap_or(false) {
+        let _ = fs::rename(&new_file, video);
+    }
+    let _ = fs::remove_file(&combined_file);
+}
+
+fn cleanup(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
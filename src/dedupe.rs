@@ -0,0 +1,251 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Number of frames sampled across a video's duration to build its fingerprint.
+const SAMPLE_FRAMES: usize = 10;
+/// Side length (in pixels) of the grayscale thumbnail each sampled frame is downscaled to.
+const THUMB_SIZE: usize = 32;
+
+const VALID_EXTENSIONS: [&str; 7] = ["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm"];
+
+/// A video's path together with its spatio-temporal perceptual hash.
+struct VideoHash {
+    path: PathBuf,
+    hash: Vec<u8>,
+}
+
+/// Minimal BK-tree keyed on Hamming distance between equal-length bit vectors.
+///
+/// Only what `dedupe` needs is implemented: inserting hashes and finding every
+/// previously-inserted hash within a given distance of a query.
+struct BkTree {
+    root: Option<Box<BkNode>>,
+}
+
+struct BkNode {
+    item: VideoHash,
+    children: Vec<(u32, Box<BkNode>)>,
+}
+
+impl BkTree {
+    fn new() -> Self {
+        BkTree { root: None }
+    }
+
+    fn insert(&mut self, item: VideoHash) {
+        match &mut self.root {
+            None => self.root = Some(Box::new(BkNode { item, children: Vec::new() })),
+            Some(root) => BkTree::insert_node(root, item),
+        }
+    }
+
+    fn insert_node(node: &mut BkNode, item: VideoHash) {
+        let distance = hamming::distance(&node.item.hash, &item.hash) as u32;
+        for (edge, child) in node.children.iter_mut() {
+            if *edge == distance {
+                BkTree::insert_node(child, item);
+                return;
+            }
+        }
+        node.children.push((distance, Box::new(BkNode { item, children: Vec::new() })));
+    }
+
+    /// Returns paths of every inserted item within `tolerance` bits of `hash`, excluding `self_path`.
+    fn find_within(&self, hash: &[u8], tolerance: u32, self_path: &Path) -> Vec<PathBuf> {
+        let mut matches = Vec::new();
+        if let Some(root) = &self.root {
+            BkTree::search_node(root, hash, tolerance, self_path, &mut matches);
+        }
+        matches
+    }
+
+    fn search_node(node: &BkNode, hash: &[u8], tolerance: u32, self_path: &Path, out: &mut Vec<PathBuf>) {
+        let distance = hamming::distance(&node.item.hash, hash) as u32;
+        if distance <= tolerance && node.item.path != self_path {
+            out.push(node.item.path.clone());
+        }
+        let lo = distance.saturating_sub(tolerance);
+        let hi = distance + tolerance;
+        for (edge, child) in &node.children {
+            if *edge >= lo && *edge <= hi {
+                BkTree::search_node(child, hash, tolerance, self_path, out);
+            }
+        }
+    }
+}
+
+/// Recursively walks `directory` collecting every file with a known video extension.
+fn walk_videos(directory: &Path) -> Vec<PathBuf> {
+    let mut videos: Vec<PathBuf> = Vec::new();
+
+    match fs::metadata(directory) {
+        Ok(metadata) => {
+            if metadata.is_file() {
+                if has_video_extension(directory) {
+                    videos.push(directory.to_path_buf());
+                }
+            } else if metadata.is_dir() {
+                if let Ok(files) = fs::read_dir(directory) {
+                    for file in files.flatten() {
+                        let path = file.path();
+                        if path.is_dir() {
+                            videos.extend(walk_videos(&path));
+                        } else if has_video_extension(&path) {
+                            videos.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            println!("Failed to get metadata for file {}: {}", directory.display(), e)
+        }
+    }
+
+    videos
+}
+
+fn has_video_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| VALID_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Reads the duration (in seconds) of `video` via `ffprobe`.
+fn probe_duration(video: &Path) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(video)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse::<f64>().ok()
+}
+
+/// Extracts the frame at `timestamp` seconds, downscaled to a `THUMB_SIZE`x`THUMB_SIZE`
+/// grayscale thumbnail, and returns its raw 8-bit pixel buffer.
+fn extract_thumbnail(video: &Path, timestamp: f64) -> Option<Vec<u8>> {
+    let output = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-ss")
+        .arg(format!("{:.3}", timestamp))
+        .arg("-i")
+        .arg(video)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-vf")
+        .arg(format!("scale={0}:{0},format=gray", THUMB_SIZE))
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("-")
+        .output()
+        .ok()?;
+    if output.stdout.len() != THUMB_SIZE * THUMB_SIZE {
+        return None;
+    }
+    Some(output.stdout)
+}
+
+/// Turns a grayscale thumbnail into an average-hash: one bit per pixel, set when the
+/// pixel is brighter than the thumbnail's mean, packed 8 bits to a byte.
+fn average_hash(thumbnail: &[u8]) -> Vec<u8> {
+    let mean = thumbnail.iter().map(|&p| p as u32).sum::<u32>() / thumbnail.len() as u32;
+    thumbnail
+        .chunks(8)
+        .map(|chunk| {
+            chunk.iter().enumerate().fold(0u8, |byte, (i, &pixel)| {
+                if pixel as u32 > mean {
+                    byte | (1 << i)
+                } else {
+                    byte
+                }
+            })
+        })
+        .collect()
+}
+
+/// Builds a spatio-temporal fingerprint for `video` by sampling `SAMPLE_FRAMES` evenly
+/// spaced frames across its duration and concatenating each frame's average-hash bits.
+fn fingerprint(video: &Path) -> Option<Vec<u8>> {
+    let duration = probe_duration(video)?;
+    if duration <= 0.0 {
+        return None;
+    }
+
+    let mut hash = Vec::with_capacity(SAMPLE_FRAMES * THUMB_SIZE * THUMB_SIZE / 8);
+    for i in 0..SAMPLE_FRAMES {
+        let timestamp = duration * (i as f64 + 0.5) / SAMPLE_FRAMES as f64;
+        let thumbnail = extract_thumbnail(video, timestamp)?;
+        hash.extend(average_hash(&thumbnail));
+    }
+    Some(hash)
+}
+
+/// Finds near-duplicate videos under `paths` and either prints the resulting clusters
+/// (`dry_run`) or just reports them for the user to act on.
+pub fn run_dedupe(paths: &[String], dry_run: bool, tolerance: u32) {
+    let mut videos: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        videos.extend(walk_videos(Path::new(path)));
+    }
+
+    let mut tree = BkTree::new();
+    let mut hashes: Vec<VideoHash> = Vec::new();
+    for video in videos {
+        match fingerprint(&video) {
+            Some(hash) => hashes.push(VideoHash { path: video, hash }),
+            None => println!("Failed to fingerprint {}, skipping.", video.display()),
+        }
+    }
+
+    for video_hash in &hashes {
+        tree.insert(VideoHash { path: video_hash.path.clone(), hash: video_hash.hash.clone() });
+    }
+
+    let mut seen: Vec<PathBuf> = Vec::new();
+    let mut clusters: Vec<Vec<PathBuf>> = Vec::new();
+    for video_hash in &hashes {
+        if seen.contains(&video_hash.path) {
+            continue;
+        }
+        let mut matches = tree.find_within(&video_hash.hash, tolerance, &video_hash.path);
+        if !matches.is_empty() {
+            matches.push(video_hash.path.clone());
+            matches.sort();
+            for m in &matches {
+                seen.push(m.clone());
+            }
+            clusters.push(matches);
+        }
+    }
+
+    if clusters.is_empty() {
+        println!("No near-duplicate videos found.");
+        return;
+    }
+
+    if dry_run {
+        println!("The following clusters of likely duplicates were found (tolerance: {} bits):", tolerance);
+        for (i, cluster) in clusters.iter().enumerate() {
+            println!("Cluster {}:", i + 1);
+            for video in cluster {
+                println!("    {}", video.display());
+            }
+        }
+        println!("Total clusters: {}", clusters.len());
+    } else {
+        println!("Found {} clusters of likely duplicates:", clusters.len());
+        for (i, cluster) in clusters.iter().enumerate() {
+            println!("Cluster {}:", i + 1);
+            for video in cluster {
+                println!("    {}", video.display());
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser};
+use clap::{Parser, Subcommand};
 use std::fs;
 use std::env;
 use std::path::{Path, PathBuf};
@@ -7,6 +7,12 @@ use indicatif::ProgressBar;
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
 
+mod chunked;
+mod dedupe;
+mod organize;
+mod probe;
+mod vmaf;
+
 #[derive(Parser)]
 #[command(name = "vidconvert-rs")]
 #[command(version = "1.0")]
@@ -33,62 +39,130 @@ struct Cli {
 
     /// Generate Subtitles using Whisper for all videos that do no contain subtitles
     #[arg(long)]
-    gen_subs: bool
+    gen_subs: bool,
+
+    /// Search for the CQ value that achieves this VMAF score instead of using a fixed preset
+    #[arg(long)]
+    target_vmaf: Option<f64>,
+
+    /// Acceptable distance from --target-vmaf before the CQ search stops
+    #[arg(long, default_value_t = 0.5)]
+    vmaf_tolerance: f64,
+
+    /// Lower bound of the CQ range searched for --target-vmaf
+    #[arg(long, default_value_t = 18)]
+    cq_min: u32,
+
+    /// Upper bound of the CQ range searched for --target-vmaf
+    #[arg(long, default_value_t = 40)]
+    cq_max: u32,
+
+    /// Split each file at scene changes and encode the chunks in parallel
+    #[arg(long)]
+    chunked: bool,
+
+    /// Number of chunks to encode in parallel when --chunked is set
+    #[arg(long, default_value_t = 4)]
+    workers: usize,
+
+    /// ffmpeg scene-change score above which a frame is treated as a chunk boundary
+    #[arg(long, default_value_t = 0.4)]
+    scene_threshold: f64,
+
+    /// Rename/move files into a Plex-style layout using TMDB metadata after transcoding
+    #[arg(long)]
+    organize: bool,
+
+    /// TMDB API key used by --organize (can also be set via the TMDB_API_KEY env var)
+    #[arg(long, env = "TMDB_API_KEY")]
+    tmdb_api_key: Option<String>,
+
+    /// Root directory the --organize layout is written under (defaults to the scanned path)
+    #[arg(long)]
+    output_root: Option<String>,
+
+    /// Spoken language to pass to whisper when generating subtitles
+    #[arg(long, default_value = "English")]
+    sub_language: String,
+
+    /// Whisper model size/name to use when generating subtitles
+    #[arg(long, default_value = "medium")]
+    whisper_model: String,
+
+    /// Max line width to pass to whisper when generating subtitles
+    #[arg(long, default_value_t = 50)]
+    max_line_width: u32,
+
+    /// After generating subtitles, also run whisper's translate task and tag its sidecar
+    /// with this language; the value only sets the file suffix and is not passed to whisper
+    #[arg(long)]
+    translate_to: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Find perceptually-similar/duplicate videos under the given paths
+    Dedupe {
+        /// Path(s) to scan for duplicates
+        paths: Vec<String>,
+
+        /// Print the clusters of likely duplicates as a dry-run listing; no files are moved
+        /// or deleted with or without this flag
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Hamming distance (in bits) at or below which two videos are considered duplicates
+        #[arg(long, default_value_t = 15)]
+        tolerance: u32,
+    },
 }
 
 fn check_for_h264(video: &Path) -> bool {
-    let ffprobe_command = Command::new("ffprobe")
-        .arg("-v")
-        .arg("error")
-        .arg("-select_streams")
-        .arg("v:0")
-        .arg("-show_entries")
-        .arg("stream=codec_name")
-        .arg("-of")
-        .arg("default=noprint_wrappers=1:nokey=1")
-        .arg(video)
-        .output();
-    if ffprobe_command.as_ref().expect("No Output from Command.").stdout.len() > 0 {
-        let output = ffprobe_command.expect("No Output from Command.").stdout.clone();
-        let codec_name = String::from_utf8_lossy(&output);
-        return codec_name.trim() == "h264"
-    }
-    else {
-        return false;
+    match probe::probe(video) {
+        Ok(media_info) => media_info.has_h264_video(),
+        Err(e) => {
+            println!("Warning | {}", e);
+            false
+        }
     }
-    
 }
 
 fn check_for_subs(video: &Path) -> bool {
     // check for embedded subs
-    let ffprobe_command = Command::new("ffprobe")
-        .arg("-v")
-        .arg("error")
-        .arg("-select_streams")
-        .arg("s")
-        .arg("-show_entries")
-        .arg("stream=index")
-        .arg("-of")
-        .arg("csv=p=0")
-        .arg(video)
-        .output();
-    // println!("ffprobe length: {}", ffprobe_command.as_ref().expect("No Output from Command.").stdout.len());
-    if ffprobe_command.as_ref().expect("No Output from Command.").stdout.len() > 0 {
+    let has_embedded_subs = match probe::probe(video) {
+        Ok(media_info) => media_info.has_subtitles(),
+        Err(e) => {
+            println!("Warning | {}", e);
+            false
+        }
+    };
+    if has_embedded_subs {
         return true;
     }
-    else {
-        // if no embedded subs, check for .srt
-        let mut srt_file = video.to_path_buf();
-        srt_file.set_extension("srt");
-        // println!("SRT exists? {}", srt_file.as_path().exists());
-        if srt_file.as_path().exists() {
-            return true;
-        }
-        else {
-            return false;
-        }
+
+    // if no embedded subs, check for a plain .srt or a language-suffixed one (video.en.srt)
+    let mut srt_file = video.to_path_buf();
+    srt_file.set_extension("srt");
+    if srt_file.as_path().exists() {
+        return true;
     }
-    
+
+    let Some(parent) = video.parent() else { return false };
+    let Some(stem) = video.file_stem().map(|s| s.to_string_lossy().to_string()) else { return false };
+    let Ok(entries) = fs::read_dir(parent) else { return false };
+    entries.filter_map(|e| e.ok()).any(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Only accept <stem>.<lang>.srt: the part between the stem and ".srt" must be a
+        // single path segment, so a sidecar for a different file (e.g. "Movie.Extended.Cut.srt"
+        // next to "Movie.mkv") isn't mistaken for this video's language-tagged subtitles.
+        match name.strip_prefix(&format!("{}.", stem)).and_then(|rest| rest.strip_suffix(".srt")) {
+            Some(lang) => !lang.is_empty() && !lang.contains('.'),
+            None => false,
+        }
+    })
 }
 
 fn convert_video(video: &Path, cli_parse: &Cli) {
@@ -96,19 +170,40 @@ fn convert_video(video: &Path, cli_parse: &Cli) {
     let temp_file = video.with_file_name("temp_file.mkv");
     let new_file = video.with_file_name("new_file.mkv");
     let preset_file = env::current_dir().expect("ERROR | Could not get current working directory.").join("presets.json");
-    let handbrake_command = Command::new("HandBrakeCLI")
-        .arg("-i")
-        .arg(video)
-        .arg("-o")
-        .arg(temp_file.clone())
-        .arg("--preset-import-file")
-        .arg(preset_file.clone())
-        .arg("--preset")
-        .arg("Fast 1080p NVENC")
-        .output();
-    if handbrake_command.as_ref().expect("ERROR | No Output from Command.").stdout.len() > 0 {
+    let mut handbrake_command = Command::new("HandBrakeCLI");
+    handbrake_command.arg("-i").arg(video).arg("-o").arg(temp_file.clone());
+
+    if let Some(target_vmaf) = cli_parse.target_vmaf {
+        let cq = vmaf::find_quality_for_target(
+            video,
+            target_vmaf,
+            cli_parse.vmaf_tolerance,
+            cli_parse.cq_min,
+            cli_parse.cq_max,
+            cli_parse.debug,
+        );
         if cli_parse.debug {
-            println!("{}", String::from_utf8_lossy(&handbrake_command.as_ref().expect("ERROR | No Output from Command.").stdout.clone()));
+            println!("VMAF search | Encoding {} at CQ {}", video.display(), cq);
+        }
+        handbrake_command
+            .arg("--preset-import-file")
+            .arg(preset_file.clone())
+            .arg("--preset")
+            .arg("Fast 1080p NVENC")
+            .arg("--quality")
+            .arg(cq.to_string());
+    } else {
+        handbrake_command
+            .arg("--preset-import-file")
+            .arg(preset_file.clone())
+            .arg("--preset")
+            .arg("Fast 1080p NVENC");
+    }
+
+    let handbrake_command = handbrake_command.output().expect("ERROR | No Output from Command.");
+    if !handbrake_command.stdout.is_empty() {
+        if cli_parse.debug {
+            println!("{}", String::from_utf8_lossy(&handbrake_command.stdout));
         }
         let mkvmerge_command = Command::new("mkvmerge")
             .arg("-o")
@@ -121,10 +216,11 @@ fn convert_video(video: &Path, cli_parse: &Cli) {
             .arg("-T")
             .arg("-M")
             .arg(temp_file.clone())
-            .output();
-        if mkvmerge_command.as_ref().expect("ERROR | No Output from Command.").stdout.len() > 0 {
+            .output()
+            .expect("ERROR | No Output from Command.");
+        if !mkvmerge_command.stdout.is_empty() {
             if cli_parse.debug {
-                println!("{}", String::from_utf8_lossy(&mkvmerge_command.as_ref().expect("ERROR | No Output from Command.").stdout.clone()));
+                println!("{}", String::from_utf8_lossy(&mkvmerge_command.stdout));
             }
             let _ = fs::rename(new_file.clone(), video);
             let _ = fs::remove_file(temp_file.clone());
@@ -132,19 +228,60 @@ fn convert_video(video: &Path, cli_parse: &Cli) {
     }
 }
 
-fn generate_subtitles(video: &Path) {
+fn generate_subtitles(video: &Path, cli_parse: &Cli) {
+    let parent = video.parent().expect("Failed to get parent directory of video path");
+    let default_srt = video.with_extension("srt");
+
     // faster-whisper-xxl.exe .\MythBusters.S06E01.James.Bond.Special.Part.1.720p.mkv --verbose true --language English --model large --max_line_width 250 -o .
     let _whisper_command = Command::new("faster-whisper-xxl")
         .arg(video)
         .arg("--language")
-        .arg("English")
+        .arg(&cli_parse.sub_language)
         .arg("--model")
-        .arg("medium")
+        .arg(&cli_parse.whisper_model)
         .arg("--max_line_width")
-        .arg("50")
+        .arg(cli_parse.max_line_width.to_string())
         .arg("-o")
-        .arg(video.parent().expect("Failed to get parent directory of video path"))
+        .arg(parent)
         .output();
+    let source_code = language_code(&cli_parse.sub_language).unwrap_or_else(|| cli_parse.sub_language.to_lowercase());
+    let _ = fs::rename(&default_srt, video.with_extension(format!("{}.srt", source_code)));
+
+    if let Some(translate_to) = &cli_parse.translate_to {
+        let _translate_command = Command::new("faster-whisper-xxl")
+            .arg(video)
+            .arg("--task")
+            .arg("translate")
+            .arg("--language")
+            .arg(&cli_parse.sub_language)
+            .arg("--model")
+            .arg(&cli_parse.whisper_model)
+            .arg("--max_line_width")
+            .arg(cli_parse.max_line_width.to_string())
+            .arg("-o")
+            .arg(parent)
+            .output();
+        let translated_code = language_code(translate_to).unwrap_or_else(|| translate_to.to_lowercase());
+        let _ = fs::rename(&default_srt, video.with_extension(format!("{}.srt", translated_code)));
+    }
+}
+
+/// Maps a handful of common whisper `--language` spellings to the short suffix used for
+/// language-tagged sidecars (`video.en.srt`). Returns `None` for anything else; callers
+/// fall back to the lowercased language string itself so the sidecar is still tagged
+/// (and never silently overwritten by a later pass).
+fn language_code(language: &str) -> Option<String> {
+    let code = match language.to_lowercase().as_str() {
+        "english" | "en" => "en",
+        "spanish" | "es" => "es",
+        "french" | "fr" => "fr",
+        "german" | "de" => "de",
+        "japanese" | "ja" => "ja",
+        "italian" | "it" => "it",
+        "portuguese" | "pt" => "pt",
+        _ => return None,
+    };
+    Some(code.to_string())
 }
 
 fn get_videos(directory: &Path, cli_parse: &Cli) -> Vec<PathBuf> {
@@ -162,31 +299,22 @@ fn get_videos(directory: &Path, cli_parse: &Cli) -> Vec<PathBuf> {
 
     match fs::metadata(directory) {
         Ok(metadata) => {
-            if metadata.is_file() && valid_extension.contains(&directory.extension().expect(&format!("ERROR | No Extension found for file {}", &directory.display())).to_string_lossy().to_lowercase()) {
-                if cli_parse.include_h264 {
-                    videos.push(directory.to_path_buf()); 
-                }
-                else if !check_for_h264(&directory) {
+            if metadata.is_file() && valid_extension.contains(&directory.extension().unwrap_or_else(|| panic!("ERROR | No Extension found for file {}", directory.display())).to_string_lossy().to_lowercase()) {
+                if cli_parse.include_h264 || !check_for_h264(directory) {
                     videos.push(directory.to_path_buf());
                 }
             }
             else if metadata.is_dir() {
                 if let Ok(files) = fs::read_dir(directory) {
-                    for file in files {
-                        if let Ok(file) = file {
-                            let path = file.path();
-                            // println!("{}", path.display());
-                            if path.is_dir() {
-                                videos.extend(get_videos(&path, &cli_parse));
-                            }
-                            else if valid_extension.contains(&path.extension().expect(&format!("ERROR | No Extension found for file {}", &path.display())).to_string_lossy().to_lowercase()) {
-                                if cli_parse.include_h264 {
-                                    videos.push(path.clone()); 
-                                }
-                                else if !check_for_h264(&path) {
-                                    videos.push(path.clone());
-                                }
-                            }
+                    for file in files.flatten() {
+                        let path = file.path();
+                        // println!("{}", path.display());
+                        if path.is_dir() {
+                            videos.extend(get_videos(&path, cli_parse));
+                        }
+                        else if valid_extension.contains(&path.extension().unwrap_or_else(|| panic!("ERROR | No Extension found for file {}", path.display())).to_string_lossy().to_lowercase())
+                            && (cli_parse.include_h264 || !check_for_h264(&path)) {
+                            videos.push(path.clone());
                         }
                     }
                 }
@@ -197,10 +325,10 @@ fn get_videos(directory: &Path, cli_parse: &Cli) -> Vec<PathBuf> {
         }
     }
 
-    return videos;
+    videos
 }
 
-fn get_videos_without_subs(directory: &Path, cli_parse: &Cli) -> Vec<PathBuf> {
+fn get_videos_without_subs(directory: &Path) -> Vec<PathBuf> {
     let mut videos: Vec<PathBuf> = Vec::new();
 
     let valid_extension = [
@@ -213,42 +341,45 @@ fn get_videos_without_subs(directory: &Path, cli_parse: &Cli) -> Vec<PathBuf> {
         String::from("webm"),
     ];
 
-    if directory.exists() && directory.is_file() && valid_extension.contains(&directory.extension().expect(&format!("ERROR | No Extension found for file {}", &directory.display())).to_string_lossy().to_lowercase()) {
-        if !check_for_subs(&directory) {
-            videos.push(directory.to_path_buf()); 
+    if directory.exists() && directory.is_file() && valid_extension.contains(&directory.extension().unwrap_or_else(|| panic!("ERROR | No Extension found for file {}", directory.display())).to_string_lossy().to_lowercase()) {
+        if !check_for_subs(directory) {
+            videos.push(directory.to_path_buf());
         }
     }
     else if directory.exists() && directory.is_dir() {
         if let Ok(files) = fs::read_dir(directory) {
-            for file in files {
-                if let Ok(file) = file {
-                    let path = file.path();
-                    //println!("Path: {}", path.display());
-                    if path.is_dir() {
-                        videos.extend(get_videos_without_subs(&path, &cli_parse));
-                    }
-                    else if valid_extension.contains(&path.extension().expect(&format!("ERROR | No Extension found for file {}", &path.display())).to_string_lossy().to_lowercase()) {
-                        if !check_for_subs(&path) {
-                            videos.push(path.clone()); 
-                        }
-                    }
+            for file in files.flatten() {
+                let path = file.path();
+                //println!("Path: {}", path.display());
+                if path.is_dir() {
+                    videos.extend(get_videos_without_subs(&path));
+                }
+                else if valid_extension.contains(&path.extension().unwrap_or_else(|| panic!("ERROR | No Extension found for file {}", path.display())).to_string_lossy().to_lowercase())
+                    && !check_for_subs(&path) {
+                    videos.push(path.clone());
                 }
             }
         }
     }
-    
-    return videos;
+
+    videos
 }
 
 fn main() {
     let cli_parse = Cli::parse();
+
+    if let Some(Commands::Dedupe { paths, dry_run, tolerance }) = &cli_parse.command {
+        dedupe::run_dedupe(paths, *dry_run, *tolerance);
+        return;
+    }
+
     for i in &cli_parse.paths {
         // println!("+ Starting vidtoolkit-rs for {}", i);
         let directory = Path::new(i);
         if !cli_parse.no_transcode {
             let videos_to_transcode = get_videos(directory, &cli_parse);
             if cli_parse.dry_run {
-                if videos_to_transcode.len() < 1 {
+                if videos_to_transcode.is_empty() {
                     println!("There are no valid files to be converted.");
                 }
                 else {
@@ -259,23 +390,38 @@ fn main() {
                     println!("Total files to convert: {}", videos_to_transcode.len());
                 }
             }
-            let pb = ProgressBar::new(videos_to_transcode.len().try_into().unwrap());
-            pb.set_position(0);
-            for video in &videos_to_transcode {
-                // Convert Video
-                if !cli_parse.no_transcode {
-                    convert_video(video, &cli_parse);
+            if cli_parse.chunked {
+                let total_chunks: usize = videos_to_transcode
+                    .iter()
+                    .map(|video| chunked::count_segments(video, cli_parse.scene_threshold))
+                    .sum();
+                let pb = ProgressBar::new(total_chunks.try_into().unwrap());
+                pb.set_position(0);
+                for video in &videos_to_transcode {
+                    if !cli_parse.no_transcode {
+                        chunked::convert_video_chunked(video, &cli_parse, cli_parse.workers, cli_parse.scene_threshold, &pb);
+                    }
                 }
-                pb.inc(1);
+                pb.finish_with_message("Encoding done for ${i.clone()}");
+            } else {
+                let pb = ProgressBar::new(videos_to_transcode.len().try_into().unwrap());
+                pb.set_position(0);
+                for video in &videos_to_transcode {
+                    // Convert Video
+                    if !cli_parse.no_transcode {
+                        convert_video(video, &cli_parse);
+                    }
+                    pb.inc(1);
+                }
+                pb.finish_with_message("Encoding done for ${i.clone()}");
             }
-            pb.finish_with_message("Encoding done for ${i.clone()}");
         }
 
         if cli_parse.gen_subs {
             // Generating Subs
-            let videos_to_generate_subs_for = get_videos_without_subs(directory, &cli_parse);
+            let videos_to_generate_subs_for = get_videos_without_subs(directory);
             if cli_parse.dry_run {
-                if videos_to_generate_subs_for.len() < 1 {
+                if videos_to_generate_subs_for.is_empty() {
                     println!("There are no valid files to have subs generated.");
                 }
                 else {
@@ -295,13 +441,33 @@ fn main() {
                     .expect("Failed to build thread pool");
                 pool.install(|| {
                     videos_to_generate_subs_for.par_iter().for_each(|video| {
-                        generate_subtitles(video);
+                        generate_subtitles(video, &cli_parse);
                         pb.inc(1);
                     });
                 });
                 pb.finish_with_message("Sub Generation done for ${i.clone()}");
             }
         }
-        
+
+        if cli_parse.organize {
+            match &cli_parse.tmdb_api_key {
+                None => println!("ERROR | --organize requires --tmdb-api-key (or the TMDB_API_KEY env var)."),
+                Some(api_key) => {
+                    let output_root = cli_parse
+                        .output_root
+                        .as_ref()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| directory.to_path_buf());
+                    let videos_to_organize = organize::walk_videos(directory);
+                    let moves = organize::plan_moves(&videos_to_organize, &output_root, api_key);
+                    if cli_parse.dry_run {
+                        organize::print_plan(&moves);
+                    } else {
+                        organize::execute_moves(&moves);
+                    }
+                }
+            }
+        }
+
     }
 }
@@ -0,0 +1,279 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+
+const TMDB_API_BASE: &str = "https://api.themoviedb.org/3";
+
+const VALID_EXTENSIONS: [&str; 7] = ["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm"];
+
+/// A single rename/move the organize pass wants to perform. `--dry-run` prints these
+/// without touching disk; otherwise they're executed in order.
+#[derive(Debug)]
+pub struct Move {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// What a source-release filename was parsed as, before TMDB resolves the canonical title.
+#[derive(Debug)]
+enum ParsedName {
+    Episode { show: String, show_id: Option<u64>, season: u32, episode: u32 },
+    Movie { title: String, year: Option<u32> },
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbSearchResponse<T> {
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbShow {
+    id: u64,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbMovie {
+    title: String,
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbEpisode {
+    name: String,
+}
+
+/// Parses a scene-release filename into show/season/episode or movie/year tokens.
+fn parse_filename(path: &Path) -> Option<ParsedName> {
+    let stem = path.file_stem()?.to_string_lossy().replace('.', " ");
+
+    let episode_re = Regex::new(r"(?i)^(.*?)[\s._-]+s(\d{1,2})e(\d{1,2})\b").unwrap();
+    if let Some(caps) = episode_re.captures(&stem) {
+        let show = clean_title(&caps[1]);
+        let season: u32 = caps[2].parse().ok()?;
+        let episode: u32 = caps[3].parse().ok()?;
+        return Some(ParsedName::Episode { show, show_id: None, season, episode });
+    }
+
+    let year_re = Regex::new(r"^(.*?)[\s._-]*\(?((?:19|20)\d{2})\)?").unwrap();
+    if let Some(caps) = year_re.captures(&stem) {
+        let title = clean_title(&caps[1]);
+        let year: u32 = caps[2].parse().ok()?;
+        return Some(ParsedName::Movie { title, year: Some(year) });
+    }
+
+    Some(ParsedName::Movie { title: clean_title(&stem), year: None })
+}
+
+/// Strips common scene-release noise (resolution, source tags, trailing separators)
+/// from a parsed title fragment.
+fn clean_title(raw: &str) -> String {
+    let noise_re = Regex::new(r"(?i)\b(1080p|720p|2160p|4k|x264|x265|h264|h265|web[- ]?dl|bluray|hdtv)\b").unwrap();
+    let cleaned = noise_re.replace_all(raw, "");
+    cleaned.trim().trim_matches(|c: char| c == '-' || c == '_' || c == '.').trim().to_string()
+}
+
+fn tmdb_get<T: serde::de::DeserializeOwned>(path: &str, query: &[(&str, &str)], api_key: &str) -> Option<T> {
+    let mut url = reqwest::Url::parse(&format!("{}{}", TMDB_API_BASE, path)).ok()?;
+    url.query_pairs_mut().append_pair("api_key", api_key);
+    for (key, value) in query {
+        url.query_pairs_mut().append_pair(key, value);
+    }
+    let response = reqwest::blocking::get(url).ok()?;
+    response.json::<T>().ok()
+}
+
+/// Resolves a parsed name against TMDB, returning the canonical show/movie/episode title(s).
+fn resolve_canonical(parsed: &ParsedName, api_key: &str) -> Option<ParsedName> {
+    match parsed {
+        ParsedName::Episode { show, season, episode, .. } => {
+            let search: TmdbSearchResponse<TmdbShow> =
+                tmdb_get("/search/tv", &[("query", show)], api_key)?;
+            let matched_show = search.results.into_iter().next()?;
+            Some(ParsedName::Episode {
+                show: matched_show.name,
+                show_id: Some(matched_show.id),
+                season: *season,
+                episode: *episode,
+            })
+        }
+        ParsedName::Movie { title, year } => {
+            let search: TmdbSearchResponse<TmdbMovie> =
+                tmdb_get("/search/movie", &[("query", title)], api_key)?;
+            let movie = search.results.into_iter().next()?;
+            let canonical_year = movie
+                .release_date
+                .as_deref()
+                .and_then(|d| d.get(0..4))
+                .and_then(|y| y.parse::<u32>().ok())
+                .or(*year);
+            Some(ParsedName::Movie { title: movie.title, year: canonical_year })
+        }
+    }
+}
+
+/// Looks up an episode's title for the `Show Name - S02E05 - Episode Title` layout.
+fn episode_title(show_id: u64, season: u32, episode: u32, api_key: &str) -> Option<String> {
+    let path = format!("/tv/{}/season/{}/episode/{}", show_id, season, episode);
+    tmdb_get::<TmdbEpisode>(&path, &[], api_key).map(|e| e.name)
+}
+
+fn sanitize_for_path(name: &str) -> String {
+    name.chars()
+        .map(|c| if "\\/:*?\"<>|".contains(c) { '_' } else { c })
+        .collect()
+}
+
+/// Builds the `Move` plan for `video` under `output_root`, consulting TMDB for the
+/// canonical title. Returns `None` if the filename couldn't be parsed; when TMDB has no
+/// match, the name parsed from the filename is used as is.
+fn plan_move(video: &Path, output_root: &Path, api_key: &str) -> Option<Move> {
+    let parsed = parse_filename(video)?;
+    let canonical = resolve_canonical(&parsed, api_key).unwrap_or(parsed);
+    let extension = video.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| String::from("mkv"));
+
+    let to = match canonical {
+        ParsedName::Episode { show, show_id, season, episode } => {
+            let show = sanitize_for_path(&show);
+            let title = show_id
+                .and_then(|id| episode_title(id, season, episode, api_key))
+                .unwrap_or_default();
+            let file_name = if title.is_empty() {
+                format!("{} - S{:02}E{:02}.{}", show, season, episode, extension)
+            } else {
+                format!("{} - S{:02}E{:02} - {}.{}", show, season, episode, sanitize_for_path(&title), extension)
+            };
+            output_root
+                .join(&show)
+                .join(format!("Season {:02}", season))
+                .join(file_name)
+        }
+        ParsedName::Movie { title, year } => {
+            let title = sanitize_for_path(&title);
+            let folder = match year {
+                Some(year) => format!("{} ({})", title, year),
+                None => title.clone(),
+            };
+            output_root.join(&folder).join(format!("{}.{}", folder, extension))
+        }
+    };
+
+    Some(Move { from: video.to_path_buf(), to })
+}
+
+/// Recursively walks `directory` collecting every file with a known video extension,
+/// regardless of codec. Unlike `get_videos`, organize needs files that are *already*
+/// h264 (the usual case right after transcoding) as well as ones that aren't.
+pub fn walk_videos(directory: &Path) -> Vec<PathBuf> {
+    let mut videos: Vec<PathBuf> = Vec::new();
+
+    match fs::metadata(directory) {
+        Ok(metadata) => {
+            if metadata.is_file() {
+                if has_video_extension(directory) {
+                    videos.push(directory.to_path_buf());
+                }
+            } else if metadata.is_dir() {
+                if let Ok(files) = fs::read_dir(directory) {
+                    for file in files.flatten() {
+                        let path = file.path();
+                        if path.is_dir() {
+                            videos.extend(walk_videos(&path));
+                        } else if has_video_extension(&path) {
+                            videos.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            println!("Failed to get metadata for file {}: {}", directory.display(), e)
+        }
+    }
+
+    videos
+}
+
+fn has_video_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| VALID_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Finds every subtitle sidecar for `video`: its plain `.srt` and any language-tagged
+/// ones generated by `--translate-to`/`--sub-language` (`video.en.srt`, `video.es.srt`, ...).
+/// Each result pairs the sidecar's path with its language tag, if any.
+fn find_subtitle_sidecars(video: &Path) -> Vec<(PathBuf, Option<String>)> {
+    let mut sidecars = Vec::new();
+
+    let plain_srt = video.with_extension("srt");
+    if plain_srt.exists() {
+        sidecars.push((plain_srt, None));
+    }
+
+    let Some(parent) = video.parent() else { return sidecars };
+    let Some(stem) = video.file_stem().map(|s| s.to_string_lossy().to_string()) else { return sidecars };
+    let Ok(entries) = fs::read_dir(parent) else { return sidecars };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(lang) = name.strip_prefix(&format!("{}.", stem)).and_then(|rest| rest.strip_suffix(".srt")) {
+            if !lang.is_empty() && !lang.contains('.') {
+                sidecars.push((entry.path(), Some(lang.to_string())));
+            }
+        }
+    }
+
+    sidecars
+}
+
+/// Builds the move plan for every video in `videos`, carrying each of the file's
+/// subtitle sidecars (plain or language-tagged) along to the same destination stem.
+pub fn plan_moves(videos: &[PathBuf], output_root: &Path, api_key: &str) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for video in videos {
+        let Some(video_move) = plan_move(video, output_root, api_key) else {
+            println!("Warning | Could not determine a destination for {}, skipping.", video.display());
+            continue;
+        };
+
+        for (srt_from, lang) in find_subtitle_sidecars(video) {
+            let srt_to = match lang {
+                Some(lang) => video_move.to.with_extension(format!("{}.srt", lang)),
+                None => video_move.to.with_extension("srt"),
+            };
+            moves.push(Move { from: srt_from, to: srt_to });
+        }
+
+        moves.push(video_move);
+    }
+    moves
+}
+
+/// Executes a move plan, creating any destination directories as needed.
+pub fn execute_moves(moves: &[Move]) {
+    for m in moves {
+        if let Some(parent) = m.to.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                println!("Warning | Failed to create directory {}: {}", parent.display(), e);
+                continue;
+            }
+        }
+        if let Err(e) = fs::rename(&m.from, &m.to) {
+            println!("Warning | Failed to move {} to {}: {}", m.from.display(), m.to.display(), e);
+        }
+    }
+}
+
+pub fn print_plan(moves: &[Move]) {
+    if moves.is_empty() {
+        println!("There are no files to organize.");
+        return;
+    }
+    println!("The following files WILL be moved:");
+    for m in moves {
+        println!("    {} -> {}", m.from.display(), m.to.display());
+    }
+    println!("Total files to move: {}", moves.len());
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::process::Command;
+use serde::Deserialize;
+
+/// Typed view of the `ffprobe -show_streams -show_format` JSON output we care about.
+#[derive(Debug, Deserialize)]
+pub struct MediaInfo {
+    pub streams: Vec<Stream>,
+    pub format: Format,
+}
+
+// index/tags aren't consulted yet, but are kept on the struct so callers can make
+// finer-grained stream decisions (e.g. by track tag) without touching the probe layer again.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct Stream {
+    pub index: u32,
+    pub codec_name: Option<String>,
+    pub codec_type: String,
+    #[serde(default)]
+    pub tags: std::collections::HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Format {
+    #[serde(default)]
+    pub duration: Option<String>,
+}
+
+impl MediaInfo {
+    pub fn has_h264_video(&self) -> bool {
+        self.streams
+            .iter()
+            .any(|s| s.codec_type == "video" && s.codec_name.as_deref() == Some("h264"))
+    }
+
+    pub fn has_subtitles(&self) -> bool {
+        self.streams.iter().any(|s| s.codec_type == "subtitle")
+    }
+}
+
+/// Runs `ffprobe -show_streams -show_format` on `video` and deserializes the result.
+///
+/// Replaces the old `stdout.len() > 0` / string-comparison probing: a single structured
+/// call that is robust to stream ordering and multi-stream files.
+pub fn probe(video: &Path) -> Result<MediaInfo, String> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("quiet")
+        .arg("-print_format")
+        .arg("json")
+        .arg("-show_streams")
+        .arg("-show_format")
+        .arg(video)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe on {}: {}", video.display(), e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe exited with a non-zero status for {}: {}",
+            video.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    serde_json::from_slice::<MediaInfo>(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output for {}: {}", video.display(), e))
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::probe;
+
+/// Number of short segments probed when searching for a CQ value.
+const PROBE_SEGMENTS: usize = 4;
+/// Length, in seconds, of each probe segment.
+const PROBE_SEGMENT_LENGTH: f64 = 2.0;
+
+/// Searches `[cq_min, cq_max]` via binary search for the HandBrake CQ value whose encoded
+/// output measures closest to `target_vmaf` (within `tolerance`), then returns it.
+///
+/// A few short segments spread across `video` are encoded and VMAF-scored at each
+/// candidate CQ so the search stays cheap relative to a full encode. Measurements are
+/// cached per-CQ so a value is never probed twice for the same video.
+pub fn find_quality_for_target(
+    video: &Path,
+    target_vmaf: f64,
+    tolerance: f64,
+    cq_min: u32,
+    cq_max: u32,
+    debug: bool,
+) -> u32 {
+    let segments = extract_probe_segments(video);
+    if segments.is_empty() {
+        println!("Warning | Could not extract probe segments for {}, falling back to CQ {}.", video.display(), cq_max);
+        return cq_max;
+    }
+
+    let mut cache: HashMap<u32, f64> = HashMap::new();
+    let mut low = cq_min;
+    let mut high = cq_max;
+    // Tracks the best candidate seen so far, in case no CQ in range lands within
+    // tolerance (e.g. a high --target-vmaf that even cq_min can't reach).
+    let mut best_cq = cq_min;
+    let mut best_diff = f64::MAX;
+
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let measured_vmaf = *cache
+            .entry(mid)
+            .or_insert_with(|| measure_vmaf_at_cq(video, &segments, mid, debug));
+
+        if debug {
+            println!("VMAF probe | CQ {} -> VMAF {:.2}", mid, measured_vmaf);
+        }
+
+        let diff = (measured_vmaf - target_vmaf).abs();
+        if diff < best_diff {
+            best_diff = diff;
+            best_cq = mid;
+        }
+
+        if diff <= tolerance {
+            break;
+        } else if measured_vmaf > target_vmaf {
+            // Quality is above target: raise CQ (lower quality, smaller file) and keep searching.
+            low = mid + 1;
+        } else {
+            // Quality is below target: lower CQ (higher quality).
+            if mid == 0 {
+                break;
+            }
+            high = mid - 1;
+        }
+    }
+
+    cleanup_segments(&segments);
+    best_cq
+}
+
+/// Extracts `PROBE_SEGMENTS` short clips spread evenly across `video`'s duration.
+fn extract_probe_segments(video: &Path) -> Vec<PathBuf> {
+    let duration = match probe::probe(video) {
+        Ok(media_info) => media_info.format.duration.and_then(|d| d.parse::<f64>().ok()),
+        Err(_) => None,
+    };
+    let Some(duration) = duration.filter(|d| *d > PROBE_SEGMENT_LENGTH) else {
+        return Vec::new();
+    };
+
+    let stem = video.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let mut segments = Vec::with_capacity(PROBE_SEGMENTS);
+    for i in 0..PROBE_SEGMENTS {
+        let start = duration * (i as f64 + 0.5) / PROBE_SEGMENTS as f64;
+        let segment_file = video.with_file_name(format!("vmaf_probe_src_{}_{}.mkv", stem, i));
+        let extract_command = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-v")
+            .arg("error")
+            .arg("-ss")
+            .arg(format!("{:.3}", start))
+            .arg("-i")
+            .arg(video)
+            .arg("-t")
+            .arg(format!("{:.3}", PROBE_SEGMENT_LENGTH))
+            .arg(&segment_file)
+            .output();
+        if extract_command.map(|o| o.status.success()).unwrap_or(false) && segment_file.exists() {
+            segments.push(segment_file);
+        }
+    }
+    segments
+}
+
+/// Encodes every probe segment at `cq` and returns the average VMAF against its source.
+fn measure_vmaf_at_cq(video: &Path, segments: &[PathBuf], cq: u32, debug: bool) -> f64 {
+    let mut scores = Vec::with_capacity(segments.len());
+    for segment in segments.iter() {
+        let segment_stem = segment.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let encoded_file = video.with_file_name(format!("vmaf_probe_enc_{}_{}.mkv", segment_stem, cq));
+        let encode_command = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-v")
+            .arg("error")
+            .arg("-i")
+            .arg(segment)
+            .arg("-c:v")
+            .arg("h264_nvenc")
+            .arg("-cq")
+            .arg(cq.to_string())
+            .arg(&encoded_file)
+            .output();
+
+        if !encode_command.map(|o| o.status.success()).unwrap_or(false) {
+            continue;
+        }
+
+        if let Some(score) = run_libvmaf(segment, &encoded_file, debug) {
+            scores.push(score);
+        }
+
+        let _ = std::fs::remove_file(&encoded_file);
+    }
+
+    if scores.is_empty() {
+        return 0.0;
+    }
+    scores.iter().sum::<f64>() / scores.len() as f64
+}
+
+/// Runs `ffmpeg -lavfi libvmaf` comparing `encoded` against `reference` and parses the score.
+fn run_libvmaf(reference: &Path, encoded: &Path, debug: bool) -> Option<f64> {
+    let vmaf_log = encoded.with_extension("vmaf.json");
+    let output = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(encoded)
+        .arg("-i")
+        .arg(reference)
+        .arg("-lavfi")
+        .arg(format!("libvmaf=log_fmt=json:log_path={}", vmaf_log.display()))
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .output();
+
+    if debug {
+        if let Ok(output) = &output {
+            println!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+    }
+
+    let log_contents = std::fs::read_to_string(&vmaf_log).ok()?;
+    let _ = std::fs::remove_file(&vmaf_log);
+    let parsed: serde_json::Value = serde_json::from_str(&log_contents).ok()?;
+    parsed["pooled_metrics"]["vmaf"]["mean"].as_f64()
+}
+
+fn cleanup_segments(segments: &[PathBuf]) {
+    for segment in segments {
+        let _ = std::fs::remove_file(segment);
+    }
+}